@@ -0,0 +1,154 @@
+use std::{
+    io::Read,
+    path::Path,
+    process::{Command, Stdio},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+/// how long an external preview command is given to produce its output
+/// before it's considered hung and killed
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// how often `run` checks whether it should cancel early, so a
+/// superseded build doesn't wait out the full `DEFAULT_TIMEOUT`
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// a user configured external preview generator: a command whose output
+/// feeds a preview, selected by matching the previewed file's name against
+/// a glob (eg `*.pdf`, `*.zip`)
+pub struct ExternalPreviewer {
+    pub glob: String,
+    pub command: Vec<String>, // argv, with "{}" replaced by the file's path
+    pub output_is_image_path: bool, // stdout is a path to an image, not text
+}
+
+/// what an external preview command produced
+pub struct ExternalPreviewOutput {
+    pub text: String,
+    pub is_image_path: bool,
+    pub glob: String, // glob of the generator which produced this output
+}
+
+/// the registry of user configured external preview generators
+pub struct ExternalPreviewers {
+    previewers: Vec<ExternalPreviewer>,
+}
+
+impl ExternalPreviewers {
+    pub fn new(previewers: Vec<ExternalPreviewer>) -> Self {
+        Self { previewers }
+    }
+
+    /// the first configured generator whose glob matches `path`, if any
+    pub fn find_for(&self, path: &Path) -> Option<&ExternalPreviewer> {
+        let file_name = path.file_name()?.to_str()?;
+        self.previewers.iter().find(|p| glob_match(&p.glob, file_name))
+    }
+
+    /// run the generator matching `path`, if any, giving it at most
+    /// `timeout` to produce its output, or until `should_cancel` (polled
+    /// at a short, fixed interval) returns true, in which case the
+    /// command is killed and `None` is returned right away. This lets a
+    /// caller observing a fresher request arrive preempt a slow or hung
+    /// command instead of waiting out the full timeout.
+    /// Runs the command and waits for it on the calling thread, so
+    /// callers should invoke this off the UI thread (the previewer
+    /// worker already does).
+    pub fn run(
+        &self,
+        path: &Path,
+        timeout: Duration,
+        should_cancel: impl Fn() -> bool,
+    ) -> Option<ExternalPreviewOutput> {
+        let previewer = self.find_for(path)?;
+        let args: Vec<String> = previewer.command.iter()
+            .map(|arg| arg.replace("{}", &path.to_string_lossy()))
+            .collect();
+        let (program, args) = args.split_first()?;
+        let mut child = match Command::new(program)
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                warn!("can't run external previewer {:?}: {:?}", previewer.command, e);
+                return None;
+            }
+        };
+        let mut stdout = child.stdout.take();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buf = String::new();
+            if let Some(stdout) = stdout.as_mut() {
+                let _ = stdout.read_to_string(&mut buf);
+            }
+            let _ = tx.send(buf);
+        });
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            if should_cancel() {
+                warn!("external previewer {:?} superseded, killing it", previewer.command);
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            if remaining.is_zero() {
+                warn!("external previewer {:?} timed out, killing it", previewer.command);
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+            match rx.recv_timeout(remaining.min(CANCEL_POLL_INTERVAL)) {
+                Ok(text) => {
+                    let status = child.wait();
+                    if !status.map(|s| s.success()).unwrap_or(false) {
+                        warn!("external previewer {:?} failed: {:?}", previewer.command, status);
+                        return None;
+                    }
+                    return Some(ExternalPreviewOutput {
+                        text,
+                        is_image_path: previewer.output_is_image_path,
+                        glob: previewer.glob.clone(),
+                    });
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }
+}
+
+/// supports a single leading `*` wildcard (eg `*.pdf`); any other
+/// character, including a `*` or `?` elsewhere in the glob, is matched
+/// literally
+fn glob_match(glob: &str, file_name: &str) -> bool {
+    match glob.strip_prefix('*') {
+        Some(suffix) => file_name.ends_with(suffix),
+        None => glob == file_name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_glob_matches_on_suffix() {
+        assert!(glob_match("*.pdf", "report.pdf"));
+        assert!(glob_match("*.pdf", ".pdf")); // suffix-only match, empty stem
+        assert!(!glob_match("*.pdf", "report.pdf.bak"));
+        assert!(!glob_match("*.pdf", "report.txt"));
+    }
+
+    #[test]
+    fn glob_without_wildcard_requires_exact_match() {
+        assert!(glob_match("Makefile", "Makefile"));
+        assert!(!glob_match("Makefile", "makefile"));
+        assert!(!glob_match("Makefile", "Makefile.bak"));
+    }
+}
@@ -0,0 +1,256 @@
+use {
+    crate::{display::W, errors::ProgramError},
+    crossterm::{QueueableCommand, terminal},
+    std::{env, io::Write},
+    termimad::Area,
+};
+
+/// which terminal graphics protocol, if any, is used to render an
+/// image preview. `Auto` detects the best one the running terminal
+/// supports and falls back to the half-block renderer broot already has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsProtocol {
+    Auto,
+    Kitty,
+    Sixel,
+    HalfBlocks,
+}
+
+impl GraphicsProtocol {
+    /// resolve `Auto` (or a forced choice) into the protocol to actually use
+    pub fn resolve(self) -> GraphicsProtocol {
+        match self {
+            GraphicsProtocol::Auto => detect_graphics_protocol(),
+            forced => forced,
+        }
+    }
+}
+
+/// best effort detection of the graphics protocol supported by the
+/// current terminal, based on the environment variables the main
+/// terminal emulators which implement one of those protocols set
+fn detect_graphics_protocol() -> GraphicsProtocol {
+    if env::var("KITTY_WINDOW_ID").is_ok()
+        || env::var("TERM").map_or(false, |t| t.contains("kitty"))
+        || env::var("TERM_PROGRAM").map_or(false, |p| p == "WezTerm")
+    {
+        return GraphicsProtocol::Kitty;
+    }
+    if env::var("TERM").map_or(false, |t| t.contains("sixel"))
+        || env::var("TERM_PROGRAM").map_or(false, |p| p == "iTerm.app" || p == "mlterm")
+    {
+        return GraphicsProtocol::Sixel;
+    }
+    GraphicsProtocol::HalfBlocks
+}
+
+/// size, in pixels, of one terminal cell, used to translate a cell
+/// rectangle (the preview area) into the pixel rectangle a graphics
+/// protocol needs
+pub fn cell_pixel_size() -> Option<(u16, u16)> {
+    let size = terminal::window_size().ok()?;
+    if size.columns == 0 || size.rows == 0 || size.width == 0 || size.height == 0 {
+        return None;
+    }
+    Some((size.width / size.columns, size.height / size.rows))
+}
+
+/// an RGBA image ready to be transmitted to the terminal
+pub struct RgbaImage<'d> {
+    pub width: u32,
+    pub height: u32,
+    pub data: &'d [u8], // width*height*4 bytes, row major
+}
+
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+/// place an image at `area` (in terminal cells) using the Kitty
+/// graphics protocol (base64-encoded RGBA sent in chunked `_G` APC
+/// escape sequences)
+pub fn place_kitty_image(
+    w: &mut W,
+    image: &RgbaImage<'_>,
+    area: &Area,
+) -> Result<(), ProgramError> {
+    use base64::Engine;
+    w.queue(crossterm::cursor::MoveTo(area.left, area.top))?;
+    let encoded = base64::engine::general_purpose::STANDARD.encode(image.data);
+    let bytes = encoded.as_bytes();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let end = (offset + KITTY_CHUNK_SIZE).min(bytes.len());
+        let more = if end < bytes.len() { 1 } else { 0 };
+        if offset == 0 {
+            write!(
+                w,
+                "\x1b_Ga=T,f=32,s={},v={},c={},r={},m={};",
+                image.width, image.height, area.width, area.height, more,
+            )?;
+        } else {
+            write!(w, "\x1b_Gm={};", more)?;
+        }
+        w.write_all(&bytes[offset..end])?;
+        write!(w, "\x1b\\")?;
+        offset = end;
+    }
+    Ok(())
+}
+
+/// delete every image previously placed with the Kitty protocol, so a
+/// resize or a selection change doesn't leave stale graphics behind
+pub fn clear_kitty_images(w: &mut W) -> Result<(), ProgramError> {
+    write!(w, "\x1b_Ga=d\x1b\\")?;
+    Ok(())
+}
+
+/// place an image at `area` using DEC Sixel. This is a minimalistic
+/// encoder: it never defines or selects a color register, so every
+/// sixel is painted in the terminal's default foreground color and the
+/// result is a monochrome silhouette (luminance thresholded to on/off)
+/// rather than a color thumbnail. Good enough to prove the protocol
+/// wiring; a real encoder (color registers, maybe dithering) is left
+/// as a follow-up, Kitty is the protocol that gives a crisp thumbnail today.
+///
+/// Unlike Kitty, Sixel has no `c=`/`r=` key to ask the terminal to scale
+/// the raster into a cell rectangle: the pixels sent *are* the pixels
+/// painted, starting at `area`'s top-left corner. So `image` is downsampled
+/// to fit `area`'s pixel footprint first (see `fit_in_area`); skipping that
+/// would paint a full-resolution image over whatever sits to the right of
+/// or below the preview panel.
+pub fn place_sixel_image(
+    w: &mut W,
+    image: &RgbaImage<'_>,
+    area: &Area,
+) -> Result<(), ProgramError> {
+    let resized = fit_in_area(image, area);
+    let scaled_image;
+    let image: &RgbaImage<'_> = match &resized {
+        Some((width, height, data)) => {
+            scaled_image = RgbaImage { width: *width, height: *height, data };
+            &scaled_image
+        }
+        None => image,
+    };
+    w.queue(crossterm::cursor::MoveTo(area.left, area.top))?;
+    write!(w, "\x1bPq")?; // enter sixel mode
+    write!(w, "\"1;1;{};{}", image.width, image.height)?;
+    for band_top in (0..image.height).step_by(6) {
+        for x in 0..image.width {
+            let mut sixel: u8 = 0;
+            for dy in 0..6u32 {
+                let y = band_top + dy;
+                if y >= image.height {
+                    continue;
+                }
+                let idx = ((y * image.width + x) * 4) as usize;
+                let lum = luminance(&image.data[idx..idx + 4]);
+                if lum > 127 {
+                    sixel |= 1 << dy;
+                }
+            }
+            write!(w, "{}", (0x3f + sixel) as char)?;
+        }
+        write!(w, "-")?; // next band
+    }
+    write!(w, "\x1b\\")?; // string terminator
+    Ok(())
+}
+
+/// downsample `image` (nearest neighbor) so it fits inside `area`'s pixel
+/// footprint, preserving aspect ratio. Returns `None` when the image
+/// already fits, or when the terminal's cell pixel size can't be
+/// determined (in which case the caller draws `image` untouched, best
+/// effort, rather than not rendering anything)
+fn fit_in_area(image: &RgbaImage<'_>, area: &Area) -> Option<(u32, u32, Vec<u8>)> {
+    let (cell_width, cell_height) = cell_pixel_size()?;
+    fit_to_pixels(
+        image,
+        area.width as u32 * cell_width as u32,
+        area.height as u32 * cell_height as u32,
+    )
+}
+
+/// the pure arithmetic behind `fit_in_area`, split out so it can be unit
+/// tested without a real terminal to query a cell pixel size from
+fn fit_to_pixels(image: &RgbaImage<'_>, max_width: u32, max_height: u32) -> Option<(u32, u32, Vec<u8>)> {
+    if image.width == 0 || image.height == 0 {
+        // a zero-length dimension would sail past the `<=` short-circuit
+        // below (0 is always `<=` anything) whenever the *other*
+        // dimension needs downscaling, then produce a `new_width`/
+        // `new_height` of at least 1 while `image.data` has zero rows or
+        // columns to sample from, panicking on the first `src_idx` lookup
+        return None;
+    }
+    if max_width == 0
+        || max_height == 0
+        || (image.width <= max_width && image.height <= max_height)
+    {
+        return None;
+    }
+    let scale = (max_width as f32 / image.width as f32).min(max_height as f32 / image.height as f32);
+    let new_width = ((image.width as f32 * scale) as u32).max(1);
+    let new_height = ((image.height as f32 * scale) as u32).max(1);
+    let mut data = vec![0u8; (new_width * new_height * 4) as usize];
+    for y in 0..new_height {
+        let src_y = (y * image.height) / new_height;
+        for x in 0..new_width {
+            let src_x = (x * image.width) / new_width;
+            let src_idx = ((src_y * image.width + src_x) * 4) as usize;
+            let dst_idx = ((y * new_width + x) * 4) as usize;
+            data[dst_idx..dst_idx + 4].copy_from_slice(&image.data[src_idx..src_idx + 4]);
+        }
+    }
+    Some((new_width, new_height, data))
+}
+
+fn luminance(rgba: &[u8]) -> u8 {
+    let [r, g, b, _] = [rgba[0], rgba[1], rgba[2], rgba[3]];
+    (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: u32, height: u32) -> (Vec<u8>, u32, u32) {
+        (vec![0u8; (width * height * 4) as usize], width, height)
+    }
+
+    #[test]
+    fn fits_already_small_enough_image() {
+        let (data, width, height) = image(10, 10);
+        let image = RgbaImage { width, height, data: &data };
+        assert!(fit_to_pixels(&image, 100, 100).is_none());
+    }
+
+    #[test]
+    fn downscales_preserving_aspect_ratio() {
+        let (data, width, height) = image(200, 100);
+        let image = RgbaImage { width, height, data: &data };
+        let (new_width, new_height, resized) = fit_to_pixels(&image, 50, 50).unwrap();
+        assert_eq!(new_width, 50);
+        assert_eq!(new_height, 25);
+        assert_eq!(resized.len(), (new_width * new_height * 4) as usize);
+    }
+
+    #[test]
+    fn zero_width_image_does_not_panic() {
+        let (data, width, height) = image(0, 100);
+        let image = RgbaImage { width, height, data: &data };
+        assert!(fit_to_pixels(&image, 50, 50).is_none());
+    }
+
+    #[test]
+    fn zero_height_image_does_not_panic() {
+        let (data, width, height) = image(100, 0);
+        let image = RgbaImage { width, height, data: &data };
+        assert!(fit_to_pixels(&image, 50, 50).is_none());
+    }
+
+    #[test]
+    fn zero_max_dimension_is_a_noop() {
+        let (data, width, height) = image(100, 100);
+        let image = RgbaImage { width, height, data: &data };
+        assert!(fit_to_pixels(&image, 0, 50).is_none());
+    }
+}
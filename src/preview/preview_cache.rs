@@ -0,0 +1,215 @@
+use {
+    std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+        time::SystemTime,
+    },
+    super::{Preview, PreviewMode},
+};
+
+/// number of previews kept in memory when not being displayed, used as
+/// `AppContext::preview_cache_capacity`'s default when not overridden in
+/// the user's config
+pub const DEFAULT_CAPACITY: usize = 20;
+
+/// the part of a preview's state which isn't intrinsic to the
+/// `Preview` value itself and which we want to restore when coming
+/// back to a file we already previewed
+pub struct CachedCursor {
+    pub selected_line: Option<usize>,
+    pub scroll: i32,
+    pub generator: Option<String>, // glob of the external generator which produced the preview, if any
+    pub mode: Option<PreviewMode>, // mode the cached preview was built with
+}
+
+struct CacheEntry<V> {
+    preview: V,
+    cursor: CachedCursor,
+    mtime: Option<SystemTime>,
+}
+
+/// a bounded, least-recently-used cache of built previews, so that
+/// going back to a file already previewed doesn't require rebuilding
+/// it and loses neither the selection nor the scroll.
+///
+/// Generic over the cached value (`V`, defaulting to `Preview`) so the
+/// LRU/mtime bookkeeping can be unit tested without building a real
+/// `Preview`, which needs a whole `AppContext` to construct.
+pub struct PreviewCache<V = Preview> {
+    capacity: usize,
+    entries: HashMap<PathBuf, CacheEntry<V>>,
+    /// most recently used path at the end
+    recency: Vec<PathBuf>,
+}
+
+impl<V> PreviewCache<V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            recency: Vec::new(),
+        }
+    }
+
+    fn touch(&mut self, path: &Path) {
+        if let Some(pos) = self.recency.iter().position(|p| p == path) {
+            self.recency.remove(pos);
+        }
+        self.recency.push(path.to_path_buf());
+    }
+
+    /// remove the least recently used entry if the cache is over capacity
+    fn evict_if_needed(&mut self) {
+        while self.entries.len() > self.capacity {
+            if self.recency.is_empty() {
+                break;
+            }
+            let lru = self.recency.remove(0);
+            self.entries.remove(&lru);
+        }
+    }
+
+    /// take the preview out of the cache for `path`, along with the
+    /// cursor it had, if it's still fresh (mtime unchanged).
+    /// A stale entry is silently dropped.
+    pub fn take_fresh(&mut self, path: &Path) -> Option<(V, CachedCursor)> {
+        let current_mtime = file_mtime(path);
+        let is_fresh = self.entries
+            .get(path)
+            .map_or(false, |e| e.mtime == current_mtime);
+        if !is_fresh {
+            self.entries.remove(path);
+            if let Some(pos) = self.recency.iter().position(|p| p == path) {
+                self.recency.remove(pos);
+            }
+            return None;
+        }
+        let entry = self.entries.remove(path)?;
+        if let Some(pos) = self.recency.iter().position(|p| p == path) {
+            self.recency.remove(pos);
+        }
+        Some((entry.preview, entry.cursor))
+    }
+
+    /// store a built preview along with its cursor, becoming the most
+    /// recently used entry
+    pub fn put(&mut self, path: PathBuf, preview: V, cursor: CachedCursor) {
+        let mtime = file_mtime(&path);
+        self.entries.insert(path.clone(), CacheEntry { preview, cursor, mtime });
+        self.touch(&path);
+        self.evict_if_needed();
+    }
+
+    /// drop the cached entry for `path`, forcing a rebuild next time
+    /// it's requested (used by `refresh`)
+    pub fn invalidate(&mut self, path: &Path) {
+        self.entries.remove(path);
+        if let Some(pos) = self.recency.iter().position(|p| p == path) {
+            self.recency.remove(pos);
+        }
+    }
+}
+
+fn file_mtime(path: &Path) -> Option<SystemTime> {
+    path.metadata().and_then(|md| md.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor() -> CachedCursor {
+        CachedCursor { selected_line: None, scroll: 0, generator: None, mode: None }
+    }
+
+    // `take_fresh`/`put` hit the filesystem for the mtime check, so tests
+    // use real temp files rather than made up paths.
+    fn temp_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("broot-preview-cache-test-{name}"));
+        std::fs::write(&path, b"content").unwrap();
+        path
+    }
+
+    #[test]
+    fn put_then_take_fresh_roundtrips() {
+        let mut cache: PreviewCache<&'static str> = PreviewCache::new(10);
+        let path = temp_file("roundtrip");
+        cache.put(path.clone(), "preview", cursor());
+        let (preview, _) = cache.take_fresh(&path).expect("entry should still be fresh");
+        assert_eq!(preview, "preview");
+    }
+
+    #[test]
+    fn take_fresh_is_a_take_not_a_peek() {
+        let mut cache: PreviewCache<&'static str> = PreviewCache::new(10);
+        let path = temp_file("take-once");
+        cache.put(path.clone(), "preview", cursor());
+        assert!(cache.take_fresh(&path).is_some());
+        assert!(cache.take_fresh(&path).is_none());
+    }
+
+    #[test]
+    fn take_fresh_drops_entry_whose_mtime_changed() {
+        let mut cache: PreviewCache<&'static str> = PreviewCache::new(10);
+        let path = temp_file("stale");
+        cache.put(path.clone(), "preview", cursor());
+        // touch the file so its mtime moves forward
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        std::fs::write(&path, b"new content").unwrap();
+        assert!(cache.take_fresh(&path).is_none());
+    }
+
+    #[test]
+    fn take_fresh_on_missing_path_is_none() {
+        let mut cache: PreviewCache<&'static str> = PreviewCache::new(10);
+        assert!(cache.take_fresh(Path::new("/does/not/exist")).is_none());
+    }
+
+    #[test]
+    fn capacity_zero_never_keeps_anything() {
+        let mut cache: PreviewCache<&'static str> = PreviewCache::new(0);
+        let path = temp_file("capacity-zero");
+        cache.put(path.clone(), "preview", cursor());
+        assert!(cache.take_fresh(&path).is_none());
+    }
+
+    #[test]
+    fn eviction_drops_the_least_recently_used_entry() {
+        let mut cache: PreviewCache<&'static str> = PreviewCache::new(2);
+        let a = temp_file("lru-a");
+        let b = temp_file("lru-b");
+        let c = temp_file("lru-c");
+        cache.put(a.clone(), "a", cursor());
+        cache.put(b.clone(), "b", cursor());
+        cache.put(c.clone(), "c", cursor());
+        assert!(cache.take_fresh(&a).is_none(), "a should have been evicted");
+        assert!(cache.take_fresh(&b).is_some());
+        assert!(cache.take_fresh(&c).is_some());
+    }
+
+    #[test]
+    fn touching_an_entry_protects_it_from_eviction() {
+        let mut cache: PreviewCache<&'static str> = PreviewCache::new(2);
+        let a = temp_file("recency-a");
+        let b = temp_file("recency-b");
+        let c = temp_file("recency-c");
+        cache.put(a.clone(), "a", cursor());
+        cache.put(b.clone(), "b", cursor());
+        // re-inserting `a` makes it the most recently used again, so the
+        // next insertion should evict `b`, not `a`
+        cache.put(a.clone(), "a2", cursor());
+        cache.put(c.clone(), "c", cursor());
+        assert!(cache.take_fresh(&b).is_none(), "b should have been evicted");
+        let (preview, _) = cache.take_fresh(&a).expect("a was refreshed, shouldn't be evicted");
+        assert_eq!(preview, "a2");
+    }
+
+    #[test]
+    fn invalidate_forces_a_rebuild() {
+        let mut cache: PreviewCache<&'static str> = PreviewCache::new(10);
+        let path = temp_file("invalidate");
+        cache.put(path.clone(), "preview", cursor());
+        cache.invalidate(&path);
+        assert!(cache.take_fresh(&path).is_none());
+    }
+}
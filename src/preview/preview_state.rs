@@ -1,5 +1,10 @@
 use {
-    super::*,
+    super::{
+        *,
+        graphics_protocol::{self, GraphicsProtocol},
+        preview_cache::{CachedCursor, PreviewCache},
+        previewer::Previewer,
+    },
     crate::{
         app::*,
         command::{Command, ScrollCommand, TriggerType},
@@ -15,7 +20,10 @@ use {
         cursor,
         QueueableCommand,
     },
-    std::path::{Path, PathBuf},
+    std::{
+        path::{Path, PathBuf},
+        sync::Arc,
+    },
     termimad::Area,
 };
 
@@ -23,15 +31,35 @@ use {
 /// It's usually the only state in its panel and is kept when
 /// the selection changes (other panels indirectly call
 /// set_selected_path).
+///
+/// relies on `AppContext` exposing `preview_cache_capacity: usize`
+/// (falling back to `preview_cache::DEFAULT_CAPACITY` when unset in the
+/// user's conf), `external_previewers: Arc<ExternalPreviewers>`, and
+/// `preview_image_protocol: GraphicsProtocol`, and on its
+/// `standard_status`'s status builder carrying a `generator: Option<String>`
+/// and a `match_position: Option<(usize, usize)>` field
 pub struct PreviewState {
     pub preview_area: Area,
     dirty: bool, // true when background must be cleared
-    path: PathBuf, // path to the previewed file
-    preview: Preview,
+    path: PathBuf, // path of the current selection, advanced as soon as it changes
+    preview_path: PathBuf, // path whose content `preview` actually holds
+    preview: Option<Preview>, // None until the first background build comes back
     pending_pattern: InputPattern, // a pattern (or not) which has not yet be applied
     filtered_preview: Option<Preview>,
     removed_pattern: InputPattern,
     prefered_mode: Option<PreviewMode>,
+    cache: PreviewCache, // previews of files we navigated away from
+    previewer: Previewer, // builds previews on a dedicated thread
+    pending_build: Option<PendingBuild>, // a build requested but not yet received
+    active_generator: Option<String>, // glob of the external generator behind `preview`, if any
+    build_error: Option<String>, // message from the last build that panicked, if any
+}
+
+/// tracks an in flight background build, so a late result can be matched
+/// to the request which triggered it (and a stale one discarded)
+struct PendingBuild {
+    generation: u64,
+    previous_path: PathBuf, // `preview_path` as it was when this build was requested
 }
 
 impl PreviewState {
@@ -39,36 +67,70 @@ impl PreviewState {
         path: PathBuf,
         pending_pattern: InputPattern,
         prefered_mode: Option<PreviewMode>,
-        con: &AppContext,
+        con: &Arc<AppContext>,
     ) -> PreviewState {
         let preview_area = Area::uninitialized(); // will be fixed at drawing time
-        let preview = Preview::new(&path, prefered_mode, con);
+        // `Previewer` needs an owned `Arc<AppContext>` to clone into its
+        // worker thread (the same way it already shares `external_previewers`
+        // across threads), not a `'static` borrow: that would demand every
+        // caller of `PreviewState::new` prove it can hand out a `'static`
+        // reference, which nothing here establishes
+        let mut previewer = Previewer::new(Arc::clone(con));
+        // the first preview is always built in the background: `display`
+        // shows a "building preview..." placeholder until it comes back,
+        // so building one synchronously here too would be wasted work
+        let generation = previewer.request(path.clone(), prefered_mode);
         PreviewState {
             preview_area,
             dirty: true,
+            preview_path: path.clone(),
+            pending_build: Some(PendingBuild {
+                generation,
+                previous_path: path.clone(),
+            }),
             path,
-            preview,
+            preview: None,
             pending_pattern,
             filtered_preview: None,
             removed_pattern: InputPattern::none(),
             prefered_mode,
+            cache: PreviewCache::new(con.preview_cache_capacity),
+            previewer,
+            active_generator: None,
+            build_error: None,
         }
     }
-    fn mut_preview(&mut self) -> &mut Preview {
-        self.filtered_preview.as_mut().unwrap_or(&mut self.preview)
+    /// the preview to apply navigation commands to: the filtered one if
+    /// there's a search active, the built one otherwise. `None` while the
+    /// first build is still in flight.
+    fn mut_preview(&mut self) -> Option<&mut Preview> {
+        self.filtered_preview.as_mut().or(self.preview.as_mut())
     }
     fn set_mode(
         &mut self,
         mode: PreviewMode,
         con: &AppContext,
     ) -> Result<AppStateCmdResult, ProgramError> {
-        if self.preview.get_mode() == Some(mode) {
+        if self.preview.as_ref().and_then(|p| p.get_mode()) == Some(mode) {
             return Ok(AppStateCmdResult::Keep);
         }
         Ok(match Preview::with_mode(&self.path, mode, con) {
             Ok(preview) => {
-                self.preview = preview;
+                self.preview = Some(preview);
+                self.preview_path = self.path.clone();
                 self.prefered_mode = Some(mode);
+                // an explicitly requested mode overrides whatever external
+                // generator was behind the previous preview
+                self.active_generator = None;
+                // a background build may still be in flight for the
+                // previous mode: if we let it land, `drain_pending_builds`
+                // would cache it under the wrong path (it caches whatever
+                // `self.preview` holds at that point, which is now this
+                // forced-mode preview, not the file it was built from) and
+                // would silently overwrite this forced mode with its stale
+                // result. Dropping it here makes the late result fall into
+                // `drain_pending_builds`'s "no build in flight anymore" case.
+                self.pending_build = None;
                 AppStateCmdResult::Keep
             }
             Err(e) => {
@@ -78,6 +140,22 @@ impl PreviewState {
             }
         })
     }
+    /// move the selection to the next (dy>0) or previous (dy<0) matching
+    /// line in the filtered preview, wrapping at the ends
+    fn move_to_match(&mut self, dy: i32) -> Result<AppStateCmdResult, ProgramError> {
+        if !self.preview.as_ref().is_some_and(|p| p.is_filterable()) {
+            return Ok(AppStateCmdResult::DisplayError(
+                "this preview can't be searched".to_string()
+            ));
+        }
+        let Some(filtered_preview) = self.filtered_preview.as_mut() else {
+            return Ok(AppStateCmdResult::DisplayError(
+                "no search pattern is active".to_string()
+            ));
+        };
+        move_match_cursor(filtered_preview, dy);
+        Ok(AppStateCmdResult::Keep)
+    }
 }
 
 impl AppState for PreviewState {
@@ -85,6 +163,8 @@ impl AppState for PreviewState {
     fn get_pending_task(&self) -> Option<&'static str> {
         if self.pending_pattern.is_some() {
             Some("searching")
+        } else if self.pending_build.is_some() {
+            Some("previewing")
         } else {
             None
         }
@@ -98,38 +178,117 @@ impl AppState for PreviewState {
         if pat.is_none() {
             if let Some(filtered_preview) = self.filtered_preview.take() {
                 let old_selection = filtered_preview.get_selected_line_number();
-                if let Some(number) = old_selection {
-                    self.preview.try_select_line_number(number);
+                if let (Some(number), Some(preview)) = (old_selection, self.preview.as_mut()) {
+                    preview.try_select_line_number(number);
                 }
                 self.removed_pattern = filtered_preview.pattern();
             }
-        } else {
-            if !self.preview.is_filterable() {
-                return Ok(AppStateCmdResult::DisplayError(
-                    "this preview can't be searched".to_string()
-                ));
-            }
+        } else if self.pending_build.is_none()
+            && self.preview.as_ref().is_some_and(|p| !p.is_filterable())
+        {
+            // if the build is still pending we don't know yet whether it
+            // will be filterable: accept the pattern optimistically, it's
+            // applied in `do_pending_task` once the preview is built
+            return Ok(AppStateCmdResult::DisplayError(
+                "this preview can't be searched".to_string()
+            ));
         }
         self.pending_pattern = pat;
         Ok(AppStateCmdResult::Keep)
     }
 
-    /// do the preview filtering if required and not yet done
+    /// apply every preview build result already sitting in the channel.
+    /// Several can have piled up since the last tick (eg one discarded by
+    /// a cache hit, one overwritten by a later selection change): drain
+    /// all of them so a stale one never delays the current one by a tick,
+    /// keeping only the one matching the in-flight request, if any.
+    fn drain_pending_builds(&mut self) {
+        while let Ok(result) = self.previewer.result_receiver.try_recv() {
+            let Some(pending) = &self.pending_build else {
+                continue; // no build in flight anymore (eg a cache hit), dropped
+            };
+            if result.generation != pending.generation {
+                continue; // superseded by a later selection change, dropped
+            }
+            let preview = match result.preview {
+                Ok(preview) => preview,
+                Err(message) => {
+                    // the build panicked: the previewer thread survived and
+                    // reported it instead of dying silently, but there's no
+                    // new content to show. Surface the failure and drop the
+                    // pending build so the panel doesn't stay stuck on
+                    // "building preview..." forever; whatever `self.preview`
+                    // held before (possibly nothing, on a first build) is
+                    // left untouched.
+                    self.build_error = Some(message);
+                    self.pending_build = None;
+                    continue;
+                }
+            };
+            self.build_error = None;
+            if let Some(previous_preview) = self.preview.take() {
+                // a refresh re-requests a build for the path it just
+                // invalidated (`previous_path == result.path`): the
+                // displaced preview here is the stale pre-edit content,
+                // caching it now would stamp it with the post-edit mtime
+                // and serve it back as "fresh" on the next navigation
+                if pending.previous_path != result.path {
+                    let previous_cursor = CachedCursor {
+                        selected_line: previous_preview.get_selected_line_number(),
+                        scroll: previous_preview.get_scroll(),
+                        generator: self.active_generator.take(),
+                        mode: previous_preview.get_mode(),
+                    };
+                    self.cache.put(pending.previous_path.clone(), previous_preview, previous_cursor);
+                }
+            }
+            self.preview = Some(preview);
+            self.preview_path = result.path;
+            self.active_generator = result.generator;
+            self.pending_build = None;
+            // a build landing is the first `display()`-worthy content
+            // change since the "building preview..." placeholder was
+            // shown, and every `display()` in between consumed `dirty`
+            // without it ever being re-armed (no area/selection change
+            // happened meanwhile): without this, the graphics placement
+            // gated on `dirty` in `display` would never fire for a
+            // freshly built image preview
+            self.dirty = true;
+        }
+    }
+
+    /// do the preview filtering if required and not yet done, and pick up
+    /// a preview built on the previewer thread if one has arrived
     fn do_pending_task(
         &mut self,
         _screen: &mut Screen,
         con: &AppContext,
         dam: &mut Dam,
     ) {
+        self.drain_pending_builds();
         if self.pending_pattern.is_some() {
+            // `self.preview` being set isn't enough: while a build is in
+            // flight it still holds the *previous* file's content (see
+            // `set_selected_path`), and filtering that now under the new
+            // file's path would leave `filtered_preview` stuck showing the
+            // old file's matches under the new name once the real build
+            // lands, since `drain_pending_builds` never touches it. Keep
+            // the pattern pending until the in-flight build (if any)
+            // resolves and `self.preview` is actually the current file.
+            if self.pending_build.is_some() {
+                return;
+            }
+            let Some(preview) = self.preview.as_mut() else {
+                return;
+            };
             let old_selection = self
                 .filtered_preview.as_ref().and_then(|p| p.get_selected_line_number())
-                .or_else(|| self.preview.get_selected_line_number());
+                .or_else(|| preview.get_selected_line_number());
             let pattern = self.pending_pattern.take();
             self.filtered_preview = time!(
                 Info,
                 "preview filtering",
-                self.preview.filtered(&self.path, pattern, dam, con),
+                preview.filtered(&self.path, pattern, dam, con),
             ); // can be None if a cancellation was required
             if let Some(ref mut filtered_preview) = self.filtered_preview {
                 if let Some(number) = old_selection {
@@ -143,25 +302,94 @@ impl AppState for PreviewState {
         &self.path
     }
 
-    fn set_selected_path(&mut self, path: PathBuf, con: &AppContext) {
+    fn set_selected_path(&mut self, path: PathBuf, _con: &AppContext) {
         if let Some(fp) = &self.filtered_preview {
             self.pending_pattern = fp.pattern();
         };
-        self.preview = Preview::new(&path, self.prefered_mode, con);
-        self.path = path;
+        if path == self.path {
+            return;
+        }
+        // the previewed file is changing: any graphics placed for the
+        // previous one must be cleared before the next `display`
+        self.dirty = true;
+        // flush any build result already in the channel before discarding
+        // or overwriting `pending_build` below, so it isn't lost
+        self.drain_pending_builds();
+        // a cached entry built under a mode other than the current
+        // (sticky, panel-wide) `prefered_mode` can't be reused as is: put
+        // it right back (it's still fresh, just not usable here) and fall
+        // through to a rebuild that honors the forced mode, instead of
+        // either silently reverting to whatever mode the file happened to
+        // be cached under, or discarding a perfectly fresh cache entry
+        let cache_hit = match self.cache.take_fresh(&path) {
+            Some((preview, cursor)) if self.prefered_mode.is_none() || self.prefered_mode == cursor.mode => {
+                Some((preview, cursor))
+            }
+            Some((preview, cursor)) => {
+                self.cache.put(path.clone(), preview, cursor);
+                None
+            }
+            None => None,
+        };
+        if let Some((mut cached_preview, cursor)) = cache_hit {
+            // cache the preview actually held by `self.preview`, under the
+            // path it was built from (`preview_path`), which may lag behind
+            // `self.path` while a build was still pending
+            let previous_path = std::mem::replace(&mut self.preview_path, path.clone());
+            if let Some(previous_preview) = self.preview.take() {
+                let previous_cursor = CachedCursor {
+                    selected_line: previous_preview.get_selected_line_number(),
+                    scroll: previous_preview.get_scroll(),
+                    generator: self.active_generator.take(),
+                    mode: previous_preview.get_mode(),
+                };
+                self.cache.put(previous_path, previous_preview, previous_cursor);
+            }
+            self.path = path;
+            if let Some(number) = cursor.selected_line {
+                cached_preview.try_select_line_number(number);
+            }
+            cached_preview.set_scroll(cursor.scroll);
+            self.preview = Some(cached_preview);
+            self.active_generator = cursor.generator;
+            self.pending_build = None;
+            self.build_error = None;
+        } else {
+            // build off the UI thread: `self.preview` keeps holding the
+            // previous file (tracked by `preview_path`) until the result
+            // comes back in `do_pending_task`, `display` shows only a
+            // loading line in the meantime
+            let generation = self.previewer.request(path.clone(), self.prefered_mode);
+            self.pending_build = Some(PendingBuild {
+                generation,
+                previous_path: self.preview_path.clone(),
+            });
+            self.path = path;
+        }
     }
 
     fn selection(&self) -> Selection<'_> {
         Selection {
             path: &self.path,
             stype: SelectionType::File,
-            line: self.preview.get_selected_line_number().unwrap_or(0),
+            line: self.preview.as_ref().and_then(|p| p.get_selected_line_number()).unwrap_or(0),
         }
     }
 
-    fn refresh(&mut self, _screen: &Screen, con: &AppContext) -> Command {
+    fn refresh(&mut self, _screen: &Screen, _con: &AppContext) -> Command {
         self.dirty = true;
-        self.set_selected_path(self.path.clone(), con);
+        self.cache.invalidate(&self.path);
+        // flush any build result already in the channel before we
+        // overwrite `pending_build` below, so it isn't lost
+        self.drain_pending_builds();
+        // rebuild off the UI thread, like any other preview build: `self.preview`
+        // keeps holding the previous content until the result comes back,
+        // `display` shows only a loading line in the meantime
+        let generation = self.previewer.request(self.path.clone(), self.prefered_mode);
+        self.pending_build = Some(PendingBuild {
+            generation,
+            previous_path: self.preview_path.clone(),
+        });
         Command::empty()
     }
 
@@ -174,7 +402,9 @@ impl AppState for PreviewState {
     ) -> Result<AppStateCmdResult, ProgramError> {
         if y >= self.preview_area.top  && y < self.preview_area.top + self.preview_area.height {
             let y = y - self.preview_area.top;
-            self.mut_preview().try_select_y(y);
+            if let Some(preview) = self.mut_preview() {
+                preview.try_select_y(y);
+            }
         }
         Ok(AppStateCmdResult::Keep)
     }
@@ -198,7 +428,22 @@ impl AppState for PreviewState {
             self.dirty = true;
             self.preview_area = preview_area;
         }
+        // captured before it's reset below: whether the area or the
+        // previewed content changed since the last `display`, the only
+        // time graphics placed in the terminal need to be touched at all
+        let needs_graphics_redraw = self.dirty;
         if self.dirty {
+            if con.preview_image_protocol.resolve() == GraphicsProtocol::Kitty {
+                // delete previously placed graphics so a resize or a
+                // selection change doesn't leave stale images behind
+                graphics_protocol::clear_kitty_images(w)?;
+            }
+            // Sixel has no standard "delete placed image" escape, so a
+            // stale Sixel raster relies on this repaint overwriting the
+            // same cells with plain text. Most terminals erase graphics
+            // that way, but it isn't guaranteed (e.g. some multiplexers),
+            // so stale graphics can survive on those setups - a known
+            // limitation, like `place_sixel_image`'s monochrome output.
             panel_skin.styles.default.queue_bg(w)?;
             screen.clear_area_to_right(w, &state_area)?;
             self.dirty = false;
@@ -217,8 +462,48 @@ impl AppState for PreviewState {
             1,
         );
         cw.fill(&styles.default, LONG_SPACE)?;
-        let preview = self.filtered_preview.as_mut().unwrap_or(&mut self.preview);
+        if self.pending_build.is_some() {
+            w.queue(cursor::MoveTo(self.preview_area.left, self.preview_area.top))?;
+            let mut cw = CropWriter::new(w, self.preview_area.width as usize);
+            cw.queue_str(&styles.default, "building preview...")?;
+            cw.fill(&styles.default, LONG_SPACE)?;
+            return Ok(());
+        }
+        // `self.preview` is normally built by the time we get here (we
+        // already returned above while `pending_build` was still set), but
+        // it can still be `None` if the very first build for this path
+        // panicked: there was never a previous preview to fall back to
+        let Some(preview) = self.filtered_preview.as_mut().or(self.preview.as_mut()) else {
+            w.queue(cursor::MoveTo(self.preview_area.left, self.preview_area.top))?;
+            let mut cw = CropWriter::new(w, self.preview_area.width as usize);
+            let message = self.build_error.as_deref().unwrap_or("can't build a preview");
+            cw.queue_str(&styles.default, message)?;
+            cw.fill(&styles.default, LONG_SPACE)?;
+            return Ok(());
+        };
         preview.display_info(w, screen, panel_skin, &info_area)?;
+        if preview.get_mode() == Some(PreviewMode::Image) {
+            if let Some(image) = preview.rgba_image() {
+                match con.preview_image_protocol.resolve() {
+                    GraphicsProtocol::Kitty => {
+                        if !needs_graphics_redraw {
+                            return Ok(());
+                        }
+                        return graphics_protocol::place_kitty_image(w, &image, &self.preview_area);
+                    }
+                    GraphicsProtocol::Sixel => {
+                        if !needs_graphics_redraw {
+                            return Ok(());
+                        }
+                        return graphics_protocol::place_sixel_image(w, &image, &self.preview_area);
+                    }
+                    GraphicsProtocol::HalfBlocks | GraphicsProtocol::Auto => {
+                        // no terminal graphics support detected: fall
+                        // back to the cell based half-block rendering
+                    }
+                }
+            }
+        }
         preview.display(w, screen, panel_skin, &self.preview_area, con)
     }
 
@@ -234,6 +519,12 @@ impl AppState for PreviewState {
         ssb.has_previous_state = has_previous_state;
         ssb.is_filtered = self.filtered_preview.is_some();
         ssb.has_removed_pattern = self.removed_pattern.is_some();
+        ssb.generator = self.active_generator.clone();
+        if let Some(filtered_preview) = &self.filtered_preview {
+            if let Some(current) = filtered_preview.get_selected_line_number() {
+                ssb.match_position = Some((current, filtered_preview.line_count()));
+            }
+        }
         ssb.status()
     }
 
@@ -255,19 +546,27 @@ impl AppState for PreviewState {
                 }
             }
             Internal::line_down => {
-                self.mut_preview().select_next_line();
+                if let Some(preview) = self.mut_preview() {
+                    preview.select_next_line();
+                }
                 Ok(AppStateCmdResult::Keep)
             }
             Internal::line_up => {
-                self.mut_preview().select_previous_line();
+                if let Some(preview) = self.mut_preview() {
+                    preview.select_previous_line();
+                }
                 Ok(AppStateCmdResult::Keep)
             }
             Internal::page_down => {
-                self.mut_preview().try_scroll(ScrollCommand::Pages(1));
+                if let Some(preview) = self.mut_preview() {
+                    preview.try_scroll(ScrollCommand::Pages(1));
+                }
                 Ok(AppStateCmdResult::Keep)
             }
             Internal::page_up => {
-                self.mut_preview().try_scroll(ScrollCommand::Pages(-1));
+                if let Some(preview) = self.mut_preview() {
+                    preview.try_scroll(ScrollCommand::Pages(-1));
+                }
                 Ok(AppStateCmdResult::Keep)
             }
             //Internal::restore_pattern => {
@@ -284,13 +583,19 @@ impl AppState for PreviewState {
                 self.on_pattern(InputPattern::none(), &cc.con)
             }
             Internal::select_first => {
-                self.mut_preview().select_first();
+                if let Some(preview) = self.mut_preview() {
+                    preview.select_first();
+                }
                 Ok(AppStateCmdResult::Keep)
             }
             Internal::select_last => {
-                self.mut_preview().select_last();
+                if let Some(preview) = self.mut_preview() {
+                    preview.select_last();
+                }
                 Ok(AppStateCmdResult::Keep)
             }
+            Internal::preview_match_down => self.move_to_match(1),
+            Internal::preview_match_up => self.move_to_match(-1),
             Internal::preview_image => self.set_mode(PreviewMode::Image, cc.con),
             Internal::preview_text => self.set_mode(PreviewMode::Text, cc.con),
             Internal::preview_binary => self.set_mode(PreviewMode::Hex, cc.con),
@@ -318,3 +623,138 @@ impl AppState for PreviewState {
     }
 
 }
+
+/// a cursor over a sequence of matched lines, with a "current" one that
+/// can be stepped forward or backward: implemented by `Preview` so
+/// `move_match_cursor`'s wrap-at-the-ends logic can be unit tested
+/// without building a real `Preview`, which needs a whole `AppContext`
+/// to construct (same trick as `PreviewCache`'s generic `V`)
+trait MatchCursor {
+    fn get_selected_line_number(&self) -> Option<usize>;
+    fn select_next_line(&mut self);
+    fn select_previous_line(&mut self);
+    fn select_first(&mut self);
+    fn select_last(&mut self);
+}
+
+impl MatchCursor for Preview {
+    fn get_selected_line_number(&self) -> Option<usize> {
+        Preview::get_selected_line_number(self)
+    }
+    fn select_next_line(&mut self) {
+        Preview::select_next_line(self)
+    }
+    fn select_previous_line(&mut self) {
+        Preview::select_previous_line(self)
+    }
+    fn select_first(&mut self) {
+        Preview::select_first(self)
+    }
+    fn select_last(&mut self) {
+        Preview::select_last(self)
+    }
+}
+
+/// step `cursor` to the next (dy>0) or previous (dy<0) match, wrapping
+/// around to the other end if it's already there
+fn move_match_cursor<C: MatchCursor>(cursor: &mut C, dy: i32) {
+    let before = cursor.get_selected_line_number();
+    if dy > 0 {
+        cursor.select_next_line();
+    } else {
+        cursor.select_previous_line();
+    }
+    if cursor.get_selected_line_number() == before {
+        // already at an end: wrap around to the other one
+        if dy > 0 {
+            cursor.select_first();
+        } else {
+            cursor.select_last();
+        }
+    }
+}
+
+#[cfg(test)]
+mod match_cursor_tests {
+    use super::*;
+
+    /// a bare bones `MatchCursor` over a fixed list of matched line
+    /// numbers, standing in for `Preview` in these tests
+    struct FakeCursor {
+        lines: Vec<usize>,
+        selected: Option<usize>, // index into `lines`, not a line number
+    }
+
+    impl MatchCursor for FakeCursor {
+        fn get_selected_line_number(&self) -> Option<usize> {
+            self.selected.map(|i| self.lines[i])
+        }
+        fn select_next_line(&mut self) {
+            if let Some(i) = self.selected {
+                if i + 1 < self.lines.len() {
+                    self.selected = Some(i + 1);
+                }
+            }
+        }
+        fn select_previous_line(&mut self) {
+            if let Some(i) = self.selected {
+                if i > 0 {
+                    self.selected = Some(i - 1);
+                }
+            }
+        }
+        fn select_first(&mut self) {
+            if !self.lines.is_empty() {
+                self.selected = Some(0);
+            }
+        }
+        fn select_last(&mut self) {
+            if !self.lines.is_empty() {
+                self.selected = Some(self.lines.len() - 1);
+            }
+        }
+    }
+
+    fn cursor() -> FakeCursor {
+        FakeCursor { lines: vec![3, 7, 12], selected: Some(0) }
+    }
+
+    #[test]
+    fn move_down_steps_to_the_next_match() {
+        let mut c = cursor();
+        move_match_cursor(&mut c, 1);
+        assert_eq!(c.get_selected_line_number(), Some(7));
+    }
+
+    #[test]
+    fn move_down_from_the_last_match_wraps_to_the_first() {
+        let mut c = cursor();
+        c.selected = Some(2);
+        move_match_cursor(&mut c, 1);
+        assert_eq!(c.get_selected_line_number(), Some(3));
+    }
+
+    #[test]
+    fn move_up_steps_to_the_previous_match() {
+        let mut c = cursor();
+        c.selected = Some(2);
+        move_match_cursor(&mut c, -1);
+        assert_eq!(c.get_selected_line_number(), Some(7));
+    }
+
+    #[test]
+    fn move_up_from_the_first_match_wraps_to_the_last() {
+        let mut c = cursor();
+        move_match_cursor(&mut c, -1);
+        assert_eq!(c.get_selected_line_number(), Some(12));
+    }
+
+    #[test]
+    fn a_single_match_stays_selected_in_either_direction() {
+        let mut c = FakeCursor { lines: vec![5], selected: Some(0) };
+        move_match_cursor(&mut c, 1);
+        assert_eq!(c.get_selected_line_number(), Some(5));
+        move_match_cursor(&mut c, -1);
+        assert_eq!(c.get_selected_line_number(), Some(5));
+    }
+}
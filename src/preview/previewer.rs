@@ -0,0 +1,147 @@
+use {
+    super::{
+        external_preview::DEFAULT_TIMEOUT,
+        Preview, PreviewMode,
+    },
+    crate::app::AppContext,
+    std::{
+        panic::{self, AssertUnwindSafe},
+        path::PathBuf,
+        sync::{mpsc, Arc},
+        thread,
+    },
+};
+
+/// a request to build a preview, sent to the previewer thread.
+/// `generation` lets the receiver recognize and discard a result
+/// computed for a selection which isn't the current one anymore.
+struct BuildRequest {
+    path: PathBuf,
+    mode: Option<PreviewMode>,
+    generation: u64,
+}
+
+/// a preview built on the previewer thread, sent back to the UI.
+/// `preview` is an `Err` when building it panicked (eg a malformed file
+/// tripping up a decoder): the worker thread survives and reports the
+/// failure instead of taking every future build down with it.
+pub struct BuildResult {
+    pub path: PathBuf,
+    pub generation: u64,
+    pub preview: Result<Preview, String>,
+    pub generator: Option<String>, // glob of the external generator used, if any
+}
+
+/// builds previews on a dedicated thread so that selecting a file
+/// which is slow to preview (a big image, a file needing hex/text
+/// decoding) doesn't stall the UI thread.
+///
+/// takes an owned `Arc<AppContext>` (rather than demanding a `'static`
+/// borrow from every caller) so it can be cloned into the worker thread
+/// to satisfy `thread::spawn`'s `'static` bound, the same way
+/// `con.external_previewers` is already shared across threads.
+pub struct Previewer {
+    request_sender: mpsc::Sender<BuildRequest>,
+    pub result_receiver: mpsc::Receiver<BuildResult>,
+    last_generation: u64,
+}
+
+impl Previewer {
+    pub fn new(con: Arc<AppContext>) -> Self {
+        let (request_sender, request_receiver) = mpsc::channel::<BuildRequest>();
+        let (result_sender, result_receiver) = mpsc::channel::<BuildResult>();
+        thread::spawn(move || {
+            while let Ok(mut request) = request_receiver.recv() {
+                // debounce: if the UI already queued fresher requests
+                // (eg the user kept an arrow key down), skip straight
+                // to the last one instead of building every file in
+                // between
+                while let Ok(fresher) = request_receiver.try_recv() {
+                    request = fresher;
+                }
+                // an external command can run for up to DEFAULT_TIMEOUT: if
+                // a fresher request shows up on the channel while it's in
+                // flight, cancel it right away and restart with that one,
+                // instead of letting it stall every already-superseded
+                // selection behind it. There's no `Dam` at hand here (it's
+                // created fresh per `do_pending_task` call on the UI
+                // thread and doesn't cross into this background thread),
+                // so the request channel itself doubles as the
+                // cancellation signal, the same way it's already used for
+                // debouncing above.
+                // an explicitly forced mode (preview_image/text/binary) must
+                // win over whatever external generator matches the file's
+                // name, the same way `PreviewState::set_mode`'s synchronous
+                // path already skips the registry entirely when a mode is
+                // forced: don't even run the external command in that case
+                let external_output = if request.mode.is_some() {
+                    None
+                } else {
+                    loop {
+                        let superseded_by = std::cell::Cell::new(None);
+                        let output = con.external_previewers.run(
+                            &request.path,
+                            DEFAULT_TIMEOUT,
+                            || match request_receiver.try_recv() {
+                                Ok(fresher) => {
+                                    superseded_by.set(Some(fresher));
+                                    true
+                                }
+                                Err(_) => false,
+                            },
+                        );
+                        match superseded_by.into_inner() {
+                            Some(mut fresher) => {
+                                while let Ok(even_fresher) = request_receiver.try_recv() {
+                                    fresher = even_fresher;
+                                }
+                                request = fresher;
+                            }
+                            None => break output,
+                        }
+                    }
+                };
+                let generator = external_output.as_ref().map(|output| output.glob.clone());
+                // a single malformed or oversized file panicking inside a
+                // decoder (eg the image crate) must not take this thread
+                // down with it: that would silently strand every future
+                // `request()` with no result ever coming back, since the
+                // channel's only sender lives on this thread
+                let path_for_panic_message = request.path.clone();
+                let preview = panic::catch_unwind(AssertUnwindSafe(|| {
+                    external_output
+                        .map(|output| Preview::from_external(&request.path, output, &con))
+                        .unwrap_or_else(|| Preview::new(&request.path, request.mode, &con))
+                })).map_err(|_| format!(
+                    "building the preview for {:?} panicked",
+                    path_for_panic_message,
+                ));
+                let result = BuildResult {
+                    path: request.path,
+                    generation: request.generation,
+                    preview,
+                    generator,
+                };
+                if result_sender.send(result).is_err() {
+                    break; // the UI thread is gone
+                }
+            }
+        });
+        Self {
+            request_sender,
+            result_receiver,
+            last_generation: 0,
+        }
+    }
+
+    /// queue a build request, returning its generation number so the
+    /// caller can later tell a stale result from the current one
+    pub fn request(&mut self, path: PathBuf, mode: Option<PreviewMode>) -> u64 {
+        self.last_generation += 1;
+        let generation = self.last_generation;
+        // the receiver is only dropped together with this previewer,
+        // so sending can't normally fail
+        let _ = self.request_sender.send(BuildRequest { path, mode, generation });
+        generation
+    }
+}